@@ -0,0 +1,46 @@
+//! `#[derive(Component)]` for `parsec`, so components don't need a
+//! hand-written `impl parsec::Component` for the common case of just
+//! picking a storage.
+
+extern crate proc_macro;
+extern crate syn;
+#[macro_use]
+extern crate quote;
+
+use proc_macro::TokenStream;
+use syn::{DeriveInput, Ident, MetaItem, NestedMetaItem};
+
+#[proc_macro_derive(Component, attributes(storage))]
+pub fn derive_component(input: TokenStream) -> TokenStream {
+    let source = input.to_string();
+    let ast = syn::parse_derive_input(&source).expect("#[derive(Component)] expects a valid struct");
+    impl_component(&ast).parse().expect("failed to parse generated Component impl")
+}
+
+/// Read the storage named in `#[storage(...)]`, defaulting to `VecStorage`
+/// when the attribute is absent.
+fn storage_ident(ast: &DeriveInput) -> Ident {
+    ast.attrs.iter()
+        .filter_map(|attr| match attr.value {
+            MetaItem::List(ref name, ref nested) if name == "storage" => nested.first(),
+            _ => None,
+        })
+        .filter_map(|item| match *item {
+            NestedMetaItem::MetaItem(MetaItem::Word(ref ident)) => Some(ident.clone()),
+            _ => None,
+        })
+        .next()
+        .unwrap_or_else(|| Ident::new("VecStorage"))
+}
+
+fn impl_component(ast: &DeriveInput) -> quote::Tokens {
+    let name = &ast.ident;
+    let storage = storage_ident(ast);
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+
+    quote! {
+        impl #impl_generics ::parsec::Component for #name #ty_generics #where_clause {
+            type Storage = ::parsec::#storage<#name #ty_generics>;
+        }
+    }
+}
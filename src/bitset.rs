@@ -0,0 +1,279 @@
+//! A hierarchical bitset used to track which entities are present in a
+//! storage without scanning every slot.
+//!
+//! The set is organised in four layers. Layer 0 is the actual membership
+//! data: one bit per entity id. Each layer above summarises 64 bits of the
+//! layer below into a single bit, so a lookup or an AND of several sets can
+//! skip whole 64-bit words (or whole words-of-words) at once instead of
+//! visiting every entity.
+
+/// Number of bits in a single word of any layer.
+const BITS: usize = 64;
+/// log2(BITS), used to shift between layers.
+const SHIFT: usize = 6;
+
+/// A raw entity id, as used to index into a bitset.
+pub type Index = usize;
+
+/// Read-only view over a hierarchical bitset.
+///
+/// Implemented both by [`BitSet`] itself and by combinators (such as the AND
+/// of two sets) so that [`Join`](../join/trait.Join.html) can walk an
+/// intersection without materialising it.
+pub trait BitSetLike {
+    /// The top-level summary word; every set bit of layer 3 means "some
+    /// entity in the 64 * 64 * 64 ids below is present".
+    fn layer3(&self) -> u64;
+    /// Summary word for layer 2 at the given index.
+    fn layer2(&self, i: usize) -> u64;
+    /// Summary word for layer 1 at the given index.
+    fn layer1(&self, i: usize) -> u64;
+    /// Data word for layer 0 at the given index.
+    fn layer0(&self, i: usize) -> u64;
+
+    /// Test whether `id` is present in the set.
+    fn contains(&self, id: Index) -> bool {
+        self.layer0(id >> SHIFT) & (1 << (id & (BITS - 1))) != 0
+    }
+
+    /// Iterate the ids present in the set, in ascending order.
+    fn iter(self) -> BitIter<Self> where Self: Sized {
+        // Read the top layer before `self` is moved into the iterator, or
+        // the walk below never has anything to descend into.
+        let layer3 = self.layer3();
+        BitIter {
+            set: self,
+            layer3,
+            layer2: 0,
+            layer1: 0,
+            layer0: 0,
+            prefix: [0; 3],
+        }
+    }
+}
+
+/// An owned hierarchical bitset.
+///
+/// `insert`/`remove` flip the bottom bit and propagate the change up through
+/// the summary layers; a summary bit is only cleared once the whole word
+/// below it becomes zero, so the top layers stay a cheap, sparse map of
+/// "there is something down here".
+#[derive(Debug, Default, Clone)]
+pub struct BitSet {
+    layer3: u64,
+    layer2: Vec<u64>,
+    layer1: Vec<u64>,
+    layer0: Vec<u64>,
+}
+
+impl BitSet {
+    /// Create an empty bitset.
+    pub fn new() -> BitSet {
+        BitSet::default()
+    }
+
+    fn extend_to(&mut self, id: Index) {
+        let w0 = id >> SHIFT;
+        while self.layer0.len() <= w0 { self.layer0.push(0); }
+        let w1 = w0 >> SHIFT;
+        while self.layer1.len() <= w1 { self.layer1.push(0); }
+        let w2 = w1 >> SHIFT;
+        while self.layer2.len() <= w2 { self.layer2.push(0); }
+    }
+
+    /// Mark `id` as present.
+    pub fn insert(&mut self, id: Index) {
+        self.extend_to(id);
+        let w0 = id >> SHIFT;
+        let w1 = w0 >> SHIFT;
+        let w2 = w1 >> SHIFT;
+        self.layer0[w0] |= 1 << (id & (BITS - 1));
+        self.layer1[w1] |= 1 << (w0 & (BITS - 1));
+        self.layer2[w2] |= 1 << (w1 & (BITS - 1));
+        self.layer3 |= 1 << (w2 & (BITS - 1));
+    }
+
+    /// Clear `id`, propagating the clear up through the summary layers when
+    /// the word it lived in becomes entirely empty.
+    pub fn remove(&mut self, id: Index) {
+        let w0 = id >> SHIFT;
+        if w0 >= self.layer0.len() {
+            return;
+        }
+        self.layer0[w0] &= !(1 << (id & (BITS - 1)));
+        if self.layer0[w0] != 0 {
+            return;
+        }
+
+        let w1 = w0 >> SHIFT;
+        self.layer1[w1] &= !(1 << (w0 & (BITS - 1)));
+        if self.layer1[w1] != 0 {
+            return;
+        }
+
+        let w2 = w1 >> SHIFT;
+        self.layer2[w2] &= !(1 << (w1 & (BITS - 1)));
+        if self.layer2[w2] != 0 {
+            return;
+        }
+
+        self.layer3 &= !(1 << (w2 & (BITS - 1)));
+    }
+}
+
+impl BitSetLike for BitSet {
+    fn layer3(&self) -> u64 { self.layer3 }
+    fn layer2(&self, i: usize) -> u64 { self.layer2.get(i).cloned().unwrap_or(0) }
+    fn layer1(&self, i: usize) -> u64 { self.layer1.get(i).cloned().unwrap_or(0) }
+    fn layer0(&self, i: usize) -> u64 { self.layer0.get(i).cloned().unwrap_or(0) }
+}
+
+impl<'a, T: BitSetLike> BitSetLike for &'a T {
+    fn layer3(&self) -> u64 { (*self).layer3() }
+    fn layer2(&self, i: usize) -> u64 { (*self).layer2(i) }
+    fn layer1(&self, i: usize) -> u64 { (*self).layer1(i) }
+    fn layer0(&self, i: usize) -> u64 { (*self).layer0(i) }
+}
+
+/// The intersection of two bitsets, computed lazily one word at a time.
+///
+/// ANDing the top layer first means whole empty regions of both sets are
+/// skipped without ever touching their lower layers.
+pub struct BitSetAnd<A, B>(pub A, pub B);
+
+impl<A: BitSetLike, B: BitSetLike> BitSetLike for BitSetAnd<A, B> {
+    fn layer3(&self) -> u64 { self.0.layer3() & self.1.layer3() }
+    fn layer2(&self, i: usize) -> u64 { self.0.layer2(i) & self.1.layer2(i) }
+    fn layer1(&self, i: usize) -> u64 { self.0.layer1(i) & self.1.layer1(i) }
+    fn layer0(&self, i: usize) -> u64 { self.0.layer0(i) & self.1.layer0(i) }
+}
+
+/// Iterator over the ids set in a [`BitSetLike`], produced by
+/// [`BitSetLike::iter`].
+pub struct BitIter<T> {
+    set: T,
+    layer3: u64,
+    layer2: u64,
+    layer1: u64,
+    layer0: u64,
+    // index of the current word in layer2, layer1, layer0 respectively
+    prefix: [usize; 3],
+}
+
+impl<T: BitSetLike> Iterator for BitIter<T> {
+    type Item = Index;
+
+    fn next(&mut self) -> Option<Index> {
+        loop {
+            if self.layer0 != 0 {
+                let bit = self.layer0.trailing_zeros() as usize;
+                self.layer0 &= self.layer0 - 1;
+                return Some((self.prefix[2] << SHIFT) | bit);
+            }
+
+            if self.layer1 != 0 {
+                let bit = self.layer1.trailing_zeros() as usize;
+                self.layer1 &= self.layer1 - 1;
+                self.prefix[2] = (self.prefix[1] << SHIFT) | bit;
+                self.layer0 = self.set.layer0(self.prefix[2]);
+                continue;
+            }
+
+            if self.layer2 != 0 {
+                let bit = self.layer2.trailing_zeros() as usize;
+                self.layer2 &= self.layer2 - 1;
+                self.prefix[1] = (self.prefix[0] << SHIFT) | bit;
+                self.layer1 = self.set.layer1(self.prefix[1]);
+                continue;
+            }
+
+            if self.layer3 != 0 {
+                let bit = self.layer3.trailing_zeros() as usize;
+                self.layer3 &= self.layer3 - 1;
+                self.prefix[0] = bit;
+                self.layer2 = self.set.layer2(self.prefix[0]);
+                continue;
+            }
+
+            return None;
+        }
+    }
+}
+
+impl BitSet {
+    /// Iterate the ids present in this set, in ascending order.
+    pub fn ids(&self) -> BitIter<&BitSet> {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn insert_and_contains() {
+        let mut set = BitSet::new();
+        set.insert(0);
+        set.insert(63);
+        set.insert(64);
+        set.insert(10_000);
+
+        assert!(set.contains(0));
+        assert!(set.contains(63));
+        assert!(set.contains(64));
+        assert!(set.contains(10_000));
+        assert!(!set.contains(1));
+        assert!(!set.contains(9_999));
+    }
+
+    #[test]
+    fn remove_clears_summary_layers() {
+        let mut set = BitSet::new();
+        set.insert(128);
+        set.remove(128);
+
+        assert!(!set.contains(128));
+        assert_eq!(set.layer3(), 0);
+    }
+
+    #[test]
+    fn iter_is_ordered() {
+        let mut set = BitSet::new();
+        let ids = [0usize, 5, 64, 130, 4_096, 10_000];
+        for &id in &ids {
+            set.insert(id);
+        }
+
+        let collected: Vec<_> = set.ids().collect();
+        assert_eq!(collected, ids.to_vec());
+    }
+
+    #[test]
+    fn trait_default_iter_is_ordered() {
+        // Exercise `BitSetLike::iter` directly (by value, as `Join` and
+        // `par_join` use it through `mask.iter()`), not `BitSet::ids`,
+        // which used to go through a separate, correct code path.
+        let mut set = BitSet::new();
+        let ids = [0usize, 5, 64, 130, 4_096, 10_000];
+        for &id in &ids {
+            set.insert(id);
+        }
+
+        let collected: Vec<_> = BitSetLike::iter(set).collect();
+        assert_eq!(collected, ids.to_vec());
+    }
+
+    #[test]
+    fn and_skips_disjoint_regions() {
+        let mut a = BitSet::new();
+        let mut b = BitSet::new();
+        a.insert(10);
+        a.insert(5_000);
+        b.insert(5_000);
+        b.insert(9_000);
+
+        let anded: Vec<_> = BitSetAnd(&a, &b).iter().collect();
+        assert_eq!(anded, vec![5_000]);
+    }
+}
@@ -0,0 +1,217 @@
+//! A storage decorator that records which entities were inserted, removed,
+//! or (potentially) modified since the last time the scheduler cleared the
+//! flags, so reactive systems can iterate only what changed.
+
+use std::marker::PhantomData;
+
+use bitset::BitSet;
+use Entity;
+use storage::{Storage, StorageBase};
+
+/// Wraps a storage `S` and tracks per-frame insert/modify/remove events for
+/// `T`, so a system can fetch a `FlaggedStorage` and react only to the
+/// entities that actually changed instead of re-scanning everything.
+///
+/// A mutable borrow via `get_mut` is treated as a potential write and marks
+/// the entity "modified" even if the caller ends up not changing anything;
+/// this mirrors the storage's own inability to see through the `&mut T` it
+/// hands out.
+#[derive(Debug)]
+pub struct FlaggedStorage<T, S> {
+    inner: S,
+    inserted: BitSet,
+    modified: BitSet,
+    removed: BitSet,
+    phantom: PhantomData<T>,
+}
+
+impl<T, S: Storage<T>> StorageBase for FlaggedStorage<T, S> {
+    fn del(&mut self, entity: Entity) {
+        self.removed.insert(entity.get_id());
+        self.inner.del(entity);
+    }
+}
+
+impl<T, S: Storage<T>> Storage<T> for FlaggedStorage<T, S> {
+    fn new() -> Self {
+        FlaggedStorage {
+            inner: S::new(),
+            inserted: BitSet::new(),
+            modified: BitSet::new(),
+            removed: BitSet::new(),
+            phantom: PhantomData,
+        }
+    }
+    fn get(&self, entity: Entity) -> Option<&T> {
+        self.inner.get(entity)
+    }
+    fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+        let value = self.inner.get_mut(entity);
+        if value.is_some() {
+            self.modified.insert(entity.get_id());
+        }
+        value
+    }
+    fn insert(&mut self, entity: Entity, value: T) {
+        self.inserted.insert(entity.get_id());
+        self.inner.insert(entity, value);
+    }
+    fn remove(&mut self, entity: Entity) -> Option<T> {
+        let value = self.inner.remove(entity);
+        if value.is_some() {
+            self.removed.insert(entity.get_id());
+        }
+        value
+    }
+}
+
+impl<T, S> FlaggedStorage<T, S> {
+    /// Ids inserted since the last [`clear_flags`](#method.clear_flags).
+    pub fn inserted(&self) -> &BitSet {
+        &self.inserted
+    }
+    /// Ids mutably borrowed (and thus potentially modified) since the last
+    /// [`clear_flags`](#method.clear_flags).
+    pub fn modified(&self) -> &BitSet {
+        &self.modified
+    }
+    /// Ids removed since the last [`clear_flags`](#method.clear_flags).
+    pub fn removed(&self) -> &BitSet {
+        &self.removed
+    }
+
+    /// Reset all change tracking so the next frame starts from a clean
+    /// slate. The flags accumulate until this is called; nothing calls it
+    /// automatically.
+    pub fn clear_flags(&mut self) {
+        self.inserted = BitSet::new();
+        self.modified = BitSet::new();
+        self.removed = BitSet::new();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use Entity;
+    use bitset::BitSetLike;
+    use storage::{Storage, VecStorage, HashMapStorage};
+    use super::*;
+
+    fn test_add<S>() where S: Storage<u32> {
+        let mut s = S::new();
+        for i in 0..1_000 {
+            s.insert(Entity::new(i, 1), i + 2718);
+        }
+
+        for i in 0..1_000 {
+            assert_eq!(*s.get(Entity::new(i, 1)).unwrap(), i + 2718);
+        }
+    }
+
+    fn test_sub<S>() where S: Storage<u32> {
+        let mut s = S::new();
+        for i in 0..1_000 {
+            s.insert(Entity::new(i, 1), i + 2718);
+        }
+
+        for i in 0..1_000 {
+            assert_eq!(s.remove(Entity::new(i, 1)).unwrap(), i + 2718);
+            assert!(s.remove(Entity::new(i, 1)).is_none());
+        }
+    }
+
+    fn test_get_mut<S>() where S: Storage<u32> {
+        let mut s = S::new();
+        for i in 0..1_000 {
+            s.insert(Entity::new(i, 1), i + 2718);
+        }
+
+        for i in 0..1_000 {
+            *s.get_mut(Entity::new(i, 1)).unwrap() -= 718;
+        }
+
+        for i in 0..1_000 {
+            assert_eq!(*s.get(Entity::new(i, 1)).unwrap(), i + 2000);
+        }
+    }
+
+    fn test_add_gen<S>() where S: Storage<u32> {
+        let mut s = S::new();
+        for i in 0..1_000 {
+            s.insert(Entity::new(i, 1), i + 2718);
+            s.insert(Entity::new(i, 2), i + 31415);
+        }
+
+        for i in 0..1_000 {
+            assert_eq!(*s.get(Entity::new(i, 2)).unwrap(), i + 31415);
+        }
+    }
+
+    fn test_sub_gen<S>() where S: Storage<u32> {
+        let mut s = S::new();
+        for i in 0..1_000 {
+            s.insert(Entity::new(i, 2), i + 2718);
+        }
+
+        for i in 0..1_000 {
+            assert!(s.remove(Entity::new(i, 1)).is_none());
+        }
+    }
+
+    #[test] fn vec_test_add() { test_add::<FlaggedStorage<u32, VecStorage<u32>>>(); }
+    #[test] fn vec_test_sub() { test_sub::<FlaggedStorage<u32, VecStorage<u32>>>(); }
+    #[test] fn vec_test_get_mut() { test_get_mut::<FlaggedStorage<u32, VecStorage<u32>>>(); }
+    #[test] fn vec_test_add_gen() { test_add_gen::<FlaggedStorage<u32, VecStorage<u32>>>(); }
+    #[test] fn vec_test_sub_gen() { test_sub_gen::<FlaggedStorage<u32, VecStorage<u32>>>(); }
+
+    #[test] fn hash_test_add() { test_add::<FlaggedStorage<u32, HashMapStorage<u32>>>(); }
+    #[test] fn hash_test_sub() { test_sub::<FlaggedStorage<u32, HashMapStorage<u32>>>(); }
+    #[test] fn hash_test_get_mut() { test_get_mut::<FlaggedStorage<u32, HashMapStorage<u32>>>(); }
+    #[test] fn hash_test_add_gen() { test_add_gen::<FlaggedStorage<u32, HashMapStorage<u32>>>(); }
+    #[test] fn hash_test_sub_gen() { test_sub_gen::<FlaggedStorage<u32, HashMapStorage<u32>>>(); }
+
+    #[test]
+    fn insert_flags_entity_as_inserted() {
+        let mut s: FlaggedStorage<u32, VecStorage<u32>> = Storage::new();
+        s.insert(Entity::new(0, 1), 7);
+
+        assert!(s.inserted().contains(0));
+        assert!(!s.modified().contains(0));
+        assert!(!s.removed().contains(0));
+    }
+
+    #[test]
+    fn get_mut_flags_entity_as_modified() {
+        let mut s: FlaggedStorage<u32, VecStorage<u32>> = Storage::new();
+        s.insert(Entity::new(0, 1), 7);
+        s.clear_flags();
+
+        *s.get_mut(Entity::new(0, 1)).unwrap() += 1;
+
+        assert!(s.modified().contains(0));
+        assert!(!s.inserted().contains(0));
+    }
+
+    #[test]
+    fn remove_flags_entity_as_removed() {
+        let mut s: FlaggedStorage<u32, VecStorage<u32>> = Storage::new();
+        s.insert(Entity::new(0, 1), 7);
+        s.clear_flags();
+
+        assert!(s.remove(Entity::new(0, 1)).is_some());
+
+        assert!(s.removed().contains(0));
+        assert!(!s.inserted().contains(0));
+    }
+
+    #[test]
+    fn clear_flags_resets_every_set() {
+        let mut s: FlaggedStorage<u32, VecStorage<u32>> = Storage::new();
+        s.insert(Entity::new(0, 1), 7);
+        s.clear_flags();
+
+        assert!(!s.inserted().contains(0));
+        assert!(!s.modified().contains(0));
+        assert!(!s.removed().contains(0));
+    }
+}
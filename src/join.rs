@@ -0,0 +1,94 @@
+//! Iterating the intersection of several component storages.
+
+use bitset::{BitSetAnd, BitSetLike, Index};
+
+/// Something that can be joined with other storages to iterate only the
+/// entities all of them have in common.
+///
+/// `Mask` identifies which entities this join contributes data for; `Value`
+/// is whatever is needed to actually fetch an item once an id is known to
+/// be present in the combined mask (typically `&S` or `&mut S`).
+pub trait Join {
+    /// The item yielded for each matching entity.
+    type Type;
+    /// The storage-specific data retained across the whole iteration.
+    type Value;
+    /// The membership mask used to intersect with other joins.
+    type Mask: BitSetLike;
+
+    /// Split `self` into the mask used for intersection and the value used
+    /// to fetch items.
+    fn open(self) -> (Self::Mask, Self::Value);
+
+    /// Fetch the item for `id`. Only called for ids the combined mask says
+    /// are present in every joined storage, so implementors may assume the
+    /// underlying data exists.
+    unsafe fn get(value: &mut Self::Value, id: Index) -> Self::Type;
+}
+
+/// Iterator produced by [`JoinExt::join`], walking the intersection of the
+/// masks of all joined storages.
+pub struct JoinIter<J: Join> {
+    keys: ::bitset::BitIter<J::Mask>,
+    value: J::Value,
+}
+
+impl<J: Join> JoinIter<J> {
+    fn new(j: J) -> Self {
+        let (mask, value) = j.open();
+        JoinIter { keys: mask.iter(), value }
+    }
+}
+
+impl<J: Join> Iterator for JoinIter<J> {
+    type Item = J::Type;
+
+    fn next(&mut self) -> Option<J::Type> {
+        self.keys.next().map(|id| unsafe { J::get(&mut self.value, id) })
+    }
+}
+
+/// Extension trait providing `.join()` on anything implementing [`Join`].
+pub trait JoinExt: Join + Sized {
+    /// Iterate the entities present in every joined storage.
+    fn join(self) -> JoinIter<Self> {
+        JoinIter::new(self)
+    }
+}
+
+impl<J: Join> JoinExt for J {}
+
+impl<A, B> Join for (A, B) where A: Join, B: Join {
+    type Type = (A::Type, B::Type);
+    type Value = (A::Value, B::Value);
+    type Mask = BitSetAnd<A::Mask, B::Mask>;
+
+    fn open(self) -> (Self::Mask, Self::Value) {
+        let (a, b) = self;
+        let (ma, va) = a.open();
+        let (mb, vb) = b.open();
+        (BitSetAnd(ma, mb), (va, vb))
+    }
+
+    unsafe fn get(value: &mut Self::Value, id: Index) -> Self::Type {
+        (A::get(&mut value.0, id), B::get(&mut value.1, id))
+    }
+}
+
+impl<A, B, C> Join for (A, B, C) where A: Join, B: Join, C: Join {
+    type Type = (A::Type, B::Type, C::Type);
+    type Value = (A::Value, B::Value, C::Value);
+    type Mask = BitSetAnd<A::Mask, BitSetAnd<B::Mask, C::Mask>>;
+
+    fn open(self) -> (Self::Mask, Self::Value) {
+        let (a, b, c) = self;
+        let (ma, va) = a.open();
+        let (mb, vb) = b.open();
+        let (mc, vc) = c.open();
+        (BitSetAnd(ma, BitSetAnd(mb, mc)), (va, vb, vc))
+    }
+
+    unsafe fn get(value: &mut Self::Value, id: Index) -> Self::Type {
+        (A::get(&mut value.0, id), B::get(&mut value.1, id), C::get(&mut value.2, id))
+    }
+}
@@ -0,0 +1,30 @@
+//! Entity-component-system core: storages, joins, and the optional
+//! parallel-iteration and serialization extensions built on top of them.
+
+#[macro_use]
+extern crate parsec_derive;
+pub use parsec_derive::*;
+
+extern crate fnv;
+extern crate rayon;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "serde")]
+extern crate bincode;
+
+mod storage;
+mod bitset;
+mod join;
+mod flagged;
+mod par_join;
+mod par_scheduler;
+#[cfg(feature = "serde")]
+mod serialize;
+
+pub use storage::{StorageBase, Storage, VecStorage, HashMapStorage, NullStorage, MaskedStorage};
+pub use bitset::{BitSet, BitSetLike, BitSetAnd, BitIter, Index};
+pub use join::{Join, JoinIter, JoinExt};
+pub use flagged::FlaggedStorage;
+pub use par_join::{ParJoin, par_join};
+#[cfg(feature = "serde")]
+pub use serialize::{SerializeStorage, Marker, SavedWorld, ComponentRegistration, TypedRegistration};
@@ -0,0 +1,88 @@
+//! Parallel join iteration, built on top of the membership bitset so a
+//! large world can split the matching entities across worker threads
+//! instead of running a join body serially.
+
+use bitset::{BitSetLike, Index};
+use join::Join;
+use storage::{MaskedStorage, Storage};
+
+/// A `Join` whose matching ids can be split into disjoint ranges and
+/// handed to different worker threads at once.
+///
+/// This is safe for any `Join` built out of `MaskedStorage`: entity ids
+/// never overlap between chunks, so each worker only ever calls `get` for
+/// ids no other worker is touching at the same time, even when `Type`
+/// borrows mutably.
+pub unsafe trait ParJoin: Join {}
+
+unsafe impl<'a, T, S: Storage<T>> ParJoin for &'a MaskedStorage<T, S> {}
+unsafe impl<'a, T, S: Storage<T>> ParJoin for &'a mut MaskedStorage<T, S> {}
+unsafe impl<A: ParJoin, B: ParJoin> ParJoin for (A, B) {}
+unsafe impl<A: ParJoin, B: ParJoin, C: ParJoin> ParJoin for (A, B, C) {}
+
+/// A raw pointer wrapper used to hand a `&mut J::Value` to several worker
+/// closures at once; sound here only because the ids each closure is given
+/// are disjoint, so no two workers ever dereference the same slot.
+struct SendPtr<T>(*mut T);
+unsafe impl<T> Send for SendPtr<T> {}
+
+/// Run `f` over every entity matched by the join `j`, splitting the
+/// matching ids evenly across `pool`'s worker threads.
+///
+/// A panic in any worker unwinds out of `rayon::Scope::spawn` as usual,
+/// which `pool.scope` re-raises once every task has finished, so a panic
+/// here surfaces the same way a panic in a non-parallel task does.
+pub fn par_join<J, F>(j: J, pool: &::rayon::ThreadPool, f: F)
+    where J: ParJoin, F: Fn(J::Type) + Sync, J::Value: Send
+{
+    let (mask, mut value) = j.open();
+    let ids: Vec<Index> = mask.iter().collect();
+    if ids.is_empty() {
+        return;
+    }
+
+    let workers = pool.current_num_threads().max(1);
+    let chunk_size = (ids.len() + workers - 1) / workers;
+
+    pool.scope(|scope| {
+        for chunk in ids.chunks(chunk_size) {
+            let value_ptr = SendPtr(&mut value as *mut J::Value);
+            let f = &f;
+            scope.spawn(move |_| {
+                let value = unsafe { &mut *value_ptr.0 };
+                for &id in chunk {
+                    let item = unsafe { J::get(value, id) };
+                    f(item);
+                }
+            });
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Mutex;
+
+    use Entity;
+    use storage::{Storage, VecStorage};
+    use super::*;
+
+    #[test]
+    fn par_join_visits_every_matching_entity() {
+        let mut storage: MaskedStorage<u32, VecStorage<u32>> = Storage::new();
+        for i in 0..256u32 {
+            storage.insert(Entity::new(i as usize, 1), i + 1);
+        }
+
+        let pool = ::rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+        let seen = Mutex::new(Vec::new());
+        par_join(&storage, &pool, |value: &u32| {
+            seen.lock().unwrap().push(*value);
+        });
+
+        let mut seen = seen.into_inner().unwrap();
+        seen.sort();
+        let expected: Vec<u32> = (1..257).collect();
+        assert_eq!(seen, expected);
+    }
+}
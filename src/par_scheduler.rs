@@ -0,0 +1,30 @@
+//! `Scheduler` entry points for parallel join iteration.
+
+use Component;
+use par_join::{self, ParJoin};
+use Scheduler;
+
+impl Scheduler {
+    /// Like [`run1w1r`](#method.run1w1r), but splits the matching entities
+    /// across the scheduler's worker pool instead of visiting them on a
+    /// single thread. A worker panic surfaces through [`wait`](#method.wait)
+    /// exactly as it does for `run1w1r`.
+    pub fn par_run1w1r<A, B, F>(&mut self, f: F)
+        where A: Component, B: Component,
+              F: Fn(&mut A, &B) + Sync + Send + 'static,
+    {
+        let pool = self.pool.clone();
+        self.run(move |arg| {
+            let (w, r) = arg.fetch(|w| (w.write::<A>(), w.read::<B>()));
+            par_join::par_join((&mut *w, &*r), &pool, |(a, b)| f(a, b));
+        });
+    }
+
+    /// Run `f` over the entities matched by the join `j`, splitting them
+    /// across the scheduler's worker pool.
+    pub fn par_join<J, F>(&self, j: J, f: F)
+        where J: ParJoin, F: Fn(J::Type) + Sync, J::Value: Send,
+    {
+        par_join::par_join(j, &self.pool, f);
+    }
+}
@@ -0,0 +1,171 @@
+//! Serde-based persistence for a [`World`](../struct.World.html) and its
+//! registered component storages. Gated behind the `serde` feature so
+//! projects that don't need save/load don't pay for the dependency.
+#![cfg(feature = "serde")]
+
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+use serde::{Serialize, Deserialize, Serializer, Deserializer};
+use serde::de::Error;
+
+use {Entity, Generation, World, Component};
+use storage::{VecStorage, HashMapStorage};
+
+/// Extends a storage with the ability to walk every `(Entity, &T)` pair it
+/// currently holds, and to insert saved pairs back in, so [`World::save`]
+/// and [`World::load`] don't need to know its internal layout.
+pub trait SerializeStorage<T> {
+    /// All living entities in this storage, alongside their data.
+    fn entries(&self) -> Vec<(Entity, &T)>;
+    /// Insert a saved `(Entity, T)` pair back into this storage on load.
+    fn restore(&mut self, entity: Entity, value: T);
+}
+
+impl<T> SerializeStorage<T> for VecStorage<T> {
+    fn entries(&self) -> Vec<(Entity, &T)> {
+        self.0.iter().enumerate()
+            .filter_map(|(id, slot)| slot.as_ref().map(|&(gen, ref value)| (Entity::new(id, gen), value)))
+            .collect()
+    }
+    fn restore(&mut self, entity: Entity, value: T) {
+        while self.0.len() <= entity.get_id() {
+            self.0.push(None);
+        }
+        self.0[entity.get_id()] = Some((entity.get_gen(), value));
+    }
+}
+
+impl<T> SerializeStorage<T> for HashMapStorage<T> {
+    fn entries(&self) -> Vec<(Entity, &T)> {
+        self.0.iter().map(|(&entity, value)| (entity, value)).collect()
+    }
+    fn restore(&mut self, entity: Entity, value: T) {
+        self.0.insert(entity, value);
+    }
+}
+
+/// A stable stand-in for an `Entity` that survives a save/load round trip.
+/// Raw ids are meaningless across runs (a freshly loaded `World`'s
+/// allocator has no reason to hand out the same ones), so every saved
+/// component instead references the position of its owning entity in the
+/// saved entity list, and `load` remaps markers back to real `Entity`
+/// handles as it restores each component.
+pub type Marker = u64;
+
+/// The whole serialized form of a `World`: the generation of every live
+/// entity (indexed by marker), plus one pre-serialized, named blob per
+/// registered component type.
+///
+/// Components are kept as opaque bytes rather than a single `HashMap` of
+/// typed values because a `World`'s registry erases the component type;
+/// each [`ComponentRegistration`] knows how to decode its own blob.
+#[derive(Serialize, Deserialize)]
+pub struct SavedWorld {
+    generations: Vec<Generation>,
+    components: HashMap<String, Vec<u8>>,
+}
+
+impl World {
+    /// Serialize every registered component storage plus the live
+    /// entity/generation table into `serializer`.
+    pub fn save<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let markers = self.marker_table();
+        let mut components = HashMap::with_capacity(self.registry.len());
+        for component in &self.registry {
+            components.insert(component.name().to_owned(), component.save(self, &markers));
+        }
+        let saved = SavedWorld { generations: self.live_generations(), components };
+        saved.serialize(serializer)
+    }
+
+    /// Reconstruct registered components and the live entity/generation
+    /// table from data written by [`save`](#method.save). Restored
+    /// entities keep their saved generation, so `Entity` handles built from
+    /// the saved markers continue to match the components attached to
+    /// them.
+    pub fn load<'de, D: Deserializer<'de>>(&mut self, deserializer: D) -> Result<(), D::Error> {
+        let saved = SavedWorld::deserialize(deserializer)?;
+        let markers = self.recreate_entities(&saved.generations);
+        for component in &self.registry {
+            if let Some(bytes) = saved.components.get(component.name()) {
+                component.load(self, &markers, bytes).map_err(D::Error::custom)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Register `T` as a component that [`save`](#method.save) and
+    /// [`load`](#method.load) should carry along, under `name`. Records a
+    /// [`TypedRegistration`] so the type-erased save/load walk in
+    /// `save`/`load` above can reach this component's `SerializeStorage`
+    /// impl. It does not register `T`'s storage itself; that is still done
+    /// by the world's ordinary component registration.
+    pub fn register_serializable<T>(&mut self, name: &'static str)
+        where T: Component + Serialize + for<'de> Deserialize<'de>,
+              T::Storage: SerializeStorage<T>,
+    {
+        self.registry.push(Box::new(TypedRegistration::<T>::new(name)));
+    }
+}
+
+/// Type-erased hook, one per registered serializable component type,
+/// letting `World::save`/`load` walk the registry without knowing any
+/// concrete component type statically.
+pub trait ComponentRegistration {
+    /// The name this component type is saved under (a `TypeId` isn't
+    /// stable across builds, so registration records a string instead).
+    fn name(&self) -> &str;
+    /// Encode this component's `(Marker, T)` pairs for every live entity.
+    fn save(&self, world: &World, markers: &HashMap<Entity, Marker>) -> Vec<u8>;
+    /// Decode and insert this component's saved pairs, remapping markers
+    /// back to the entities just recreated in `markers`. Errors (e.g. a
+    /// save file from an incompatible version) are returned rather than
+    /// panicking, since a bad save file is untrusted input, not a bug.
+    fn load(&self, world: &mut World, markers: &[Entity], bytes: &[u8]) -> Result<(), String>;
+}
+
+/// The `ComponentRegistration` [`World::register_serializable`](../struct.World.html#method.register_serializable)
+/// installs for every component whose type and storage both support
+/// serialization.
+pub struct TypedRegistration<T> {
+    name: &'static str,
+    phantom: PhantomData<T>,
+}
+
+impl<T> TypedRegistration<T> {
+    /// Build a registration entry saved under `name` (conventionally the
+    /// component's type name).
+    pub fn new(name: &'static str) -> Self {
+        TypedRegistration { name, phantom: PhantomData }
+    }
+}
+
+impl<T> ComponentRegistration for TypedRegistration<T>
+    where T: Component + Serialize + for<'de> Deserialize<'de>,
+          T::Storage: SerializeStorage<T>,
+{
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn save(&self, world: &World, markers: &HashMap<Entity, Marker>) -> Vec<u8> {
+        let storage = world.storage::<T>();
+        let entries: Vec<(Marker, &T)> = storage.entries().into_iter()
+            .filter_map(|(entity, value)| markers.get(&entity).map(|&marker| (marker, value)))
+            .collect();
+        ::bincode::serialize(&entries).expect("component data failed to serialize")
+    }
+
+    fn load(&self, world: &mut World, markers: &[Entity], bytes: &[u8]) -> Result<(), String> {
+        let entries: Vec<(Marker, T)> = ::bincode::deserialize(bytes)
+            .map_err(|e| format!("saved data for component '{}' is corrupt: {}", self.name, e))?;
+        let mut storage = world.storage_mut::<T>();
+        for (marker, value) in entries {
+            let entity = *markers.get(marker as usize)
+                .ok_or_else(|| format!("marker {} out of range for {} saved entities", marker, markers.len()))?;
+            storage.restore(entity, value);
+        }
+        Ok(())
+    }
+}
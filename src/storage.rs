@@ -3,6 +3,8 @@ use std::hash::BuildHasherDefault;
 use fnv::FnvHasher;
 
 use {Entity, Generation};
+use bitset::{BitSet, BitSetLike};
+use join::Join;
 
 
 /// Base trait for a component storage that is used as a trait object.
@@ -102,10 +104,194 @@ impl<T> Storage<T> for HashMapStorage<T> {
     }
 }
 
+/// Storage for zero-sized marker/tag components (e.g. "is enemy", "is
+/// dirty"). Keeps a membership bitset and no per-entity data; `get`/`get_mut`
+/// hand out a reference to a single instance held by the storage rather
+/// than storing one per entity, since every value of a zero-sized `T` is
+/// indistinguishable from any other.
+///
+/// Like `VecStorage`, it still tracks the generation each id was last
+/// inserted with, so a stale `Entity` handle (e.g. one pointing at an id
+/// that has since been recycled by a new entity) is correctly rejected by
+/// `get`/`get_mut`/`remove` instead of matching by id alone.
+#[derive(Debug)]
+pub struct NullStorage<T> {
+    mask: BitSet,
+    generations: Vec<Generation>,
+    value: T,
+}
+
+impl<T> NullStorage<T> {
+    fn live(&self, entity: Entity) -> bool {
+        let id = entity.get_id();
+        self.mask.contains(id) && self.generations.get(id) == Some(&entity.get_gen())
+    }
+}
+
+impl<T: Default> StorageBase for NullStorage<T> {
+    fn del(&mut self, entity: Entity) {
+        self.mask.remove(entity.get_id());
+    }
+}
+impl<T: Default> Storage<T> for NullStorage<T> {
+    fn new() -> Self {
+        // A zero-sized `T` has exactly one value, so `Default::default()`
+        // stands in for every entity's data; a non-zero-sized `T` would
+        // have its single instance silently shared (and overwritten) by
+        // every entity, so guard against misuse here.
+        debug_assert_eq!(::std::mem::size_of::<T>(), 0,
+            "NullStorage only supports zero-sized components");
+        NullStorage { mask: BitSet::new(), generations: Vec::new(), value: T::default() }
+    }
+    fn get(&self, entity: Entity) -> Option<&T> {
+        if self.live(entity) {
+            Some(&self.value)
+        } else {
+            None
+        }
+    }
+    fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+        if self.live(entity) {
+            Some(&mut self.value)
+        } else {
+            None
+        }
+    }
+    fn insert(&mut self, entity: Entity, value: T) {
+        let id = entity.get_id();
+        self.value = value;
+        self.mask.insert(id);
+        while self.generations.len() <= id {
+            self.generations.push(entity.get_gen());
+        }
+        self.generations[id] = entity.get_gen();
+    }
+    fn remove(&mut self, entity: Entity) -> Option<T> {
+        if self.live(entity) {
+            self.mask.remove(entity.get_id());
+            Some(T::default())
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a, T: Default> Join for &'a NullStorage<T> {
+    type Type = &'a T;
+    type Value = &'a NullStorage<T>;
+    type Mask = &'a BitSet;
+
+    fn open(self) -> (Self::Mask, Self::Value) {
+        (&self.mask, self)
+    }
+    unsafe fn get(v: &mut Self::Value, _id: ::bitset::Index) -> &'a T {
+        &v.value
+    }
+}
+
+
+/// Wraps a storage `S` with a membership bitset, so callers can ask "which
+/// entities have this component" and [`Join`](../join/trait.Join.html) can
+/// intersect several of these cheaply instead of scanning every slot.
+///
+/// Alongside the mask it keeps the generation each present id was last
+/// inserted with, so a `Join` can rebuild a real `Entity` for a raw id and
+/// go through the normal generation-checked `get`/`get_mut`.
+#[derive(Debug)]
+pub struct MaskedStorage<T, S> {
+    mask: BitSet,
+    generations: Vec<Generation>,
+    inner: S,
+    phantom: ::std::marker::PhantomData<T>,
+}
+
+impl<T, S: Storage<T>> StorageBase for MaskedStorage<T, S> {
+    fn del(&mut self, entity: Entity) {
+        self.mask.remove(entity.get_id());
+        self.inner.del(entity);
+    }
+}
+
+impl<T, S: Storage<T>> Storage<T> for MaskedStorage<T, S> {
+    fn new() -> Self {
+        MaskedStorage {
+            mask: BitSet::new(),
+            generations: Vec::new(),
+            inner: S::new(),
+            phantom: ::std::marker::PhantomData,
+        }
+    }
+    fn get(&self, entity: Entity) -> Option<&T> {
+        let value = self.inner.get(entity);
+        debug_assert_eq!(value.is_some(), self.mask.contains(entity.get_id()));
+        value
+    }
+    fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+        let mask = &self.mask;
+        let id = entity.get_id();
+        let value = self.inner.get_mut(entity);
+        debug_assert_eq!(value.is_some(), mask.contains(id));
+        value
+    }
+    fn insert(&mut self, entity: Entity, value: T) {
+        let id = entity.get_id();
+        self.mask.insert(id);
+        while self.generations.len() <= id {
+            self.generations.push(entity.get_gen());
+        }
+        self.generations[id] = entity.get_gen();
+        self.inner.insert(entity, value);
+    }
+    fn remove(&mut self, entity: Entity) -> Option<T> {
+        let value = self.inner.remove(entity);
+        // Only clear the mask once we know the entity actually had data to
+        // remove; a generation mismatch leaves `inner` untouched, and
+        // clearing the bit anyway would desync the mask from data that is
+        // still live.
+        if value.is_some() {
+            self.mask.remove(entity.get_id());
+        }
+        value
+    }
+}
+
+impl<'a, T, S: Storage<T>> Join for &'a MaskedStorage<T, S> {
+    type Type = &'a T;
+    type Value = &'a MaskedStorage<T, S>;
+    type Mask = &'a BitSet;
+
+    fn open(self) -> (Self::Mask, Self::Value) {
+        (&self.mask, self)
+    }
+    unsafe fn get(v: &mut Self::Value, id: ::bitset::Index) -> &'a T {
+        let entity = Entity::new(id, v.generations[id]);
+        v.inner.get(entity).expect("id present in mask but missing from storage")
+    }
+}
+
+impl<'a, T, S: Storage<T>> Join for &'a mut MaskedStorage<T, S> {
+    type Type = &'a mut T;
+    type Value = &'a mut MaskedStorage<T, S>;
+    type Mask = &'a BitSet;
+
+    fn open(self) -> (Self::Mask, Self::Value) {
+        // Safety: the returned mask only borrows `self.mask`, which is
+        // never touched again through `self` while the join runs; the
+        // `Value` keeps the rest of `self` reachable for mutation.
+        let mask: &'a BitSet = unsafe { &*(&self.mask as *const BitSet) };
+        (mask, self)
+    }
+    unsafe fn get(v: &mut Self::Value, id: ::bitset::Index) -> &'a mut T {
+        let entity = Entity::new(id, v.generations[id]);
+        let value: *mut T = v.inner.get_mut(entity).expect("id present in mask but missing from storage");
+        &mut *value
+    }
+}
 
 #[cfg(test)]
 mod test {
     use Entity;
+    use join::JoinExt;
     use super::*;
 
     fn test_add<S>() where S: Storage<u32> {
@@ -183,5 +369,113 @@ mod test {
     #[test] fn hash_test_get_mut() { test_get_mut::<HashMapStorage<u32>>(); }
     #[test] fn hash_test_add_gen() { test_add_gen::<HashMapStorage<u32>>(); }
     #[test] fn hash_test_sub_gen() { test_sub_gen::<HashMapStorage<u32>>(); }
+
+    #[test] fn masked_vec_test_add() { test_add::<MaskedStorage<u32, VecStorage<u32>>>(); }
+    #[test] fn masked_vec_test_sub() { test_sub::<MaskedStorage<u32, VecStorage<u32>>>(); }
+    #[test] fn masked_vec_test_get_mut() { test_get_mut::<MaskedStorage<u32, VecStorage<u32>>>(); }
+    #[test] fn masked_vec_test_add_gen() { test_add_gen::<MaskedStorage<u32, VecStorage<u32>>>(); }
+    #[test] fn masked_vec_test_sub_gen() { test_sub_gen::<MaskedStorage<u32, VecStorage<u32>>>(); }
+
+    #[test] fn masked_hash_test_add() { test_add::<MaskedStorage<u32, HashMapStorage<u32>>>(); }
+    #[test] fn masked_hash_test_sub() { test_sub::<MaskedStorage<u32, HashMapStorage<u32>>>(); }
+    #[test] fn masked_hash_test_get_mut() { test_get_mut::<MaskedStorage<u32, HashMapStorage<u32>>>(); }
+    #[test] fn masked_hash_test_add_gen() { test_add_gen::<MaskedStorage<u32, HashMapStorage<u32>>>(); }
+    #[test] fn masked_hash_test_sub_gen() { test_sub_gen::<MaskedStorage<u32, HashMapStorage<u32>>>(); }
+
+    #[test]
+    fn masked_remove_mismatched_generation_keeps_mask_in_sync() {
+        let mut s: MaskedStorage<u32, VecStorage<u32>> = Storage::new();
+        s.insert(Entity::new(0, 2), 42);
+
+        // Wrong generation: the remove must be a no-op, including for the
+        // mask, or a later `Join` thinks the entity is gone even though its
+        // data is still live in `inner`.
+        assert!(s.remove(Entity::new(0, 1)).is_none());
+        assert_eq!(*s.get(Entity::new(0, 2)).unwrap(), 42);
+        assert_eq!((&s).join().count(), 1);
+    }
+
+    #[test]
+    fn masked_join_yields_intersection() {
+        let mut a: MaskedStorage<u32, VecStorage<u32>> = Storage::new();
+        let mut b: MaskedStorage<bool, HashMapStorage<bool>> = Storage::new();
+
+        for i in 0..10 {
+            a.insert(Entity::new(i, 1), i as u32);
+        }
+        for i in 5..15 {
+            b.insert(Entity::new(i, 1), true);
+        }
+
+        let mut values: Vec<u32> = (&a, &b).join().map(|(v, _)| *v).collect();
+        values.sort();
+        assert_eq!(values, vec![5, 6, 7, 8, 9]);
+    }
+
+    #[derive(Default, Debug, PartialEq)]
+    struct Tag;
+
+    fn test_zst_add<S>() where S: Storage<Tag> {
+        let mut s = S::new();
+        for i in 0..1_000 {
+            s.insert(Entity::new(i, 1), Tag);
+        }
+
+        for i in 0..1_000 {
+            assert!(s.get(Entity::new(i, 1)).is_some());
+        }
+    }
+
+    fn test_zst_sub<S>() where S: Storage<Tag> {
+        let mut s = S::new();
+        for i in 0..1_000 {
+            s.insert(Entity::new(i, 1), Tag);
+        }
+
+        for i in 0..1_000 {
+            assert!(s.remove(Entity::new(i, 1)).is_some());
+            assert!(s.remove(Entity::new(i, 1)).is_none());
+        }
+    }
+
+    fn test_zst_add_gen<S>() where S: Storage<Tag> {
+        let mut s = S::new();
+        for i in 0..1_000 {
+            s.insert(Entity::new(i, 1), Tag);
+            s.insert(Entity::new(i, 2), Tag);
+        }
+
+        for i in 0..1_000 {
+            assert!(s.get(Entity::new(i, 2)).is_some());
+        }
+    }
+
+    fn test_zst_sub_gen<S>() where S: Storage<Tag> {
+        let mut s = S::new();
+        for i in 0..1_000 {
+            s.insert(Entity::new(i, 2), Tag);
+        }
+
+        for i in 0..1_000 {
+            assert!(s.remove(Entity::new(i, 1)).is_none());
+        }
+    }
+
+    #[test] fn null_test_add() { test_zst_add::<NullStorage<Tag>>(); }
+    #[test] fn null_test_sub() { test_zst_sub::<NullStorage<Tag>>(); }
+    #[test] fn null_test_add_gen() { test_zst_add_gen::<NullStorage<Tag>>(); }
+    #[test] fn null_test_sub_gen() { test_zst_sub_gen::<NullStorage<Tag>>(); }
+
+    #[test]
+    fn null_get_rejects_recycled_id_with_stale_generation() {
+        let mut s: NullStorage<Tag> = Storage::new();
+        s.insert(Entity::new(0, 1), Tag);
+        s.remove(Entity::new(0, 1));
+
+        // id 0 recycled by a new entity at a later generation.
+        s.insert(Entity::new(0, 2), Tag);
+        assert!(s.get(Entity::new(0, 2)).is_some());
+        assert!(s.get(Entity::new(0, 1)).is_none());
+    }
 }
 